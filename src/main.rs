@@ -1,21 +1,32 @@
 #![deny(unused_crate_dependencies)]
 
 use axum::{
-    body::Body,
-    extract::{DefaultBodyLimit, Multipart, Query},
-    http::{header, StatusCode},
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Multipart, Path, Query},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{get, post},
-    Router,
+    Json, Router,
+};
+use cairo_lang_starknet_classes::{
+    casm_contract_class::CasmContractClass, contract_class::ContractClass as SierraContractClass,
 };
-use cairo_lang_starknet_classes::casm_contract_class::CasmContractClass;
 use cairo_vm::{program_hash::compute_program_hash_chain, types::program::Program};
 use dotenv::dotenv;
-use serde::Deserialize;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest as _, Sha256};
 use sqlx::Pool;
 use sqlx::{postgres::PgPoolOptions, types::Uuid};
 use starknet_crypto::FieldElement;
-use std::{env, io::Cursor, sync::Arc};
+use std::{
+    collections::HashMap,
+    env,
+    io::{Cursor, Read, Write},
+    sync::Arc,
+};
+use thiserror::Error;
 use tokio_util::io::ReaderStream;
 
 #[tokio::main]
@@ -44,20 +55,37 @@ async fn main() {
 
     let db_pool = Arc::new(pool);
 
+    spawn_hash_job_workers(Arc::clone(&db_pool), 2);
+    spawn_stale_job_reaper(Arc::clone(&db_pool));
+
     // build our application with a route
     let app = Router::new()
         .route(
             "/get-program",
             get({
                 let db_pool = Arc::clone(&db_pool);
-                move |program| get_program(program, db_pool)
+                move |headers, program| get_program(headers, program, db_pool)
             }),
         )
         .route(
             "/upload-program",
             post({
                 let db_pool = Arc::clone(&db_pool);
-                move |multipart| upload_program(multipart, db_pool)
+                move |headers, multipart| upload_program(headers, multipart, db_pool)
+            }),
+        )
+        .route(
+            "/index/:prefix",
+            get({
+                let db_pool = Arc::clone(&db_pool);
+                move |prefix| get_index(prefix, db_pool)
+            }),
+        )
+        .route(
+            "/job/:id",
+            get({
+                let db_pool = Arc::clone(&db_pool);
+                move |id| get_job(id, db_pool)
             }),
         )
         .layer(DefaultBodyLimit::disable());
@@ -66,129 +94,737 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// Errors surfaced to clients as `{ "error": "...", "detail": "..." }` with
+/// an appropriate status code, instead of bare 4xx/5xx responses.
+#[derive(Error, Debug)]
+enum AppError {
+    #[error("invalid multipart upload")]
+    InvalidMultipart,
+    #[error("unsupported compiler version: {0}")]
+    UnsupportedCompilerVersion(String),
+    #[error("malformed casm contract class")]
+    MalformedCasm,
+    #[error("malformed sierra contract class")]
+    MalformedSierra,
+    #[error("malformed program")]
+    MalformedProgram,
+    #[error("failed to compute program hash")]
+    HashComputationFailed,
+    #[error("failed to (de)compress program bytes")]
+    CompressionFailed,
+    #[error("program not found")]
+    NotFound,
+    #[error("checksum mismatch: uploaded content does not match X-Expected-Sha256")]
+    ChecksumMismatch,
+    #[error("stored content failed integrity verification")]
+    CorruptedContent,
+    #[error("database error")]
+    DbError(#[from] sqlx::Error),
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            AppError::InvalidMultipart
+            | AppError::UnsupportedCompilerVersion(_)
+            | AppError::MalformedCasm
+            | AppError::MalformedSierra
+            | AppError::MalformedProgram
+            | AppError::ChecksumMismatch => StatusCode::BAD_REQUEST,
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::DbError(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+            AppError::HashComputationFailed
+            | AppError::CompressionFailed
+            | AppError::CorruptedContent
+            | AppError::DbError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        // Driver errors can embed constraint/table/column names and the
+        // offending values, so keep them out of the client-facing detail
+        // and only log them server-side.
+        let detail = if let AppError::DbError(err) = &self {
+            tracing::error!(error = %err, "database error");
+            "internal error".to_string()
+        } else {
+            self.to_string()
+        };
+
+        let body = Json(json!({
+            "error": status.canonical_reason().unwrap_or("error"),
+            "detail": detail,
+        }));
+
+        (status, body).into_response()
+    }
+}
+
 async fn get_program(
+    headers: HeaderMap,
     program: Query<GetProgram>,
     db_pool: Arc<Pool<sqlx::Postgres>>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, AppError> {
     let program_hash = &program.program_hash;
 
-    let row = sqlx::query!("SELECT code FROM programs WHERE hash = $1", program_hash)
-        .fetch_one(&*db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let row = sqlx::query!(
+        "SELECT code, checksum, encoding, kind, contract_id FROM programs WHERE hash = $1",
+        program_hash
+    )
+    .fetch_one(&*db_pool)
+    .await?;
+
+    let row = match program.kind.as_deref() {
+        Some(requested_kind) if requested_kind != row.kind => {
+            let contract_id = row.contract_id.ok_or(AppError::NotFound)?;
+            sqlx::query!(
+                "SELECT code, checksum, encoding, kind, contract_id FROM programs \
+                 WHERE contract_id = $1 AND kind = $2",
+                contract_id,
+                requested_kind
+            )
+            .fetch_one(&*db_pool)
+            .await?
+        }
+        _ => row,
+    };
+
+    let is_gzip = row.encoding == "gzip";
+    let verify = program.verify.as_deref() == Some("1");
+    let client_accepts_gzip = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("gzip"));
 
-    let code = row.code;
+    let (code, content_encoding) = if is_gzip && client_accepts_gzip && !verify {
+        (row.code, Some("gzip"))
+    } else if is_gzip {
+        (gunzip(&row.code)?, None)
+    } else {
+        (row.code, None)
+    };
 
+    if verify {
+        let actual = hex::encode(Sha256::digest(&code));
+        if actual != row.checksum {
+            return Err(AppError::CorruptedContent);
+        }
+    }
+
+    let digest_header = format!("sha-256={}", row.checksum);
     let stream = ReaderStream::new(Cursor::new(code));
     let body = Body::from_stream(stream);
 
-    let response = Response::builder()
+    let mut response = Response::builder()
         .header(header::CONTENT_TYPE, "application/json")
         .header(
             header::CONTENT_DISPOSITION,
             format!("attachment; filename=\"{}.json\"", program_hash),
         )
+        .header("Digest", digest_header);
+
+    if let Some(encoding) = content_encoding {
+        response = response.header(header::CONTENT_ENCODING, encoding);
+    }
+
+    let response = response
         .body(body)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| AppError::CorruptedContent)?;
 
     Ok(response)
 }
 
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut decoder = GzDecoder::new(bytes);
+    let mut decompressed = Vec::new();
+    decoder
+        .read_to_end(&mut decompressed)
+        .map_err(|_| AppError::CompressionFailed)?;
+    Ok(decompressed)
+}
+
+fn gzip(bytes: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(bytes)
+        .map_err(|_| AppError::CompressionFailed)?;
+    encoder.finish().map_err(|_| AppError::CompressionFailed)
+}
+
 #[derive(Deserialize)]
 struct GetProgram {
     program_hash: String,
+    verify: Option<String>,
+    kind: Option<String>,
+}
+
+/// One line of the sparse index for a given hash prefix, as described by
+/// `GET /index/{prefix}`.
+#[derive(Serialize)]
+struct IndexRecord {
+    hash: Option<String>,
+    version: i32,
+    compiler_version: String,
+    yanked: bool,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+async fn get_index(
+    Path(prefix): Path<String>,
+    db_pool: Arc<Pool<sqlx::Postgres>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pattern = format!("{}%", prefix);
+
+    let rows = sqlx::query!(
+        "SELECT hash, version, compiler_version, yanked, created_at FROM programs WHERE hash LIKE $1",
+        pattern
+    )
+    .fetch_all(&*db_pool)
+    .await?;
+
+    let body = rows
+        .into_iter()
+        .map(|row| {
+            let record = IndexRecord {
+                hash: row.hash,
+                version: row.version,
+                compiler_version: row.compiler_version,
+                yanked: row.yanked,
+                created_at: row.created_at,
+            };
+            serde_json::to_string(&record).unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        body,
+    ))
 }
 
 async fn upload_program(
+    headers: HeaderMap,
     mut multipart: Multipart,
     db_pool: Arc<Pool<sqlx::Postgres>>,
-) -> Result<String, StatusCode> {
-    let mut version: i32 = 0;
-    let mut program_data = None;
-    #[allow(unused_assignments)]
-    let mut program_hash_hex = String::new();
+) -> Result<Json<UploadResponse>, AppError> {
+    let mut fields: HashMap<String, Bytes> = HashMap::new();
 
-    while let Some(field) = multipart.next_field().await.unwrap() {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| AppError::InvalidMultipart)?
+    {
         let name = field.name().unwrap_or_default().to_string();
-        if name == "program" {
-            let raw_data = field.bytes().await.unwrap();
-            let compiler_version = get_compiler_version(raw_data.to_vec()).unwrap();
-            println!("Compiler version: {}", compiler_version);
-            version = compiler_version.split('.').collect::<Vec<&str>>()[0]
-                .parse::<i32>()
-                .unwrap();
-            program_data = Some(raw_data);
+        let bytes = field
+            .bytes()
+            .await
+            .map_err(|_| AppError::InvalidMultipart)?;
+        fields.insert(name, bytes);
+    }
+
+    if let (Some(sierra), Some(casm)) = (fields.remove("sierra"), fields.remove("casm")) {
+        return upload_bundle(headers, sierra, casm, db_pool).await;
+    }
+
+    let data = fields.remove("program").ok_or(AppError::InvalidMultipart)?;
+    upload_single(headers, data, db_pool).await
+}
+
+/// Uploads a single artifact: either a v0 bootloader `program` or a standalone
+/// v2 `casm` class, as produced by the original bootloader-style flow.
+async fn upload_single(
+    headers: HeaderMap,
+    data: Bytes,
+    db_pool: Arc<Pool<sqlx::Postgres>>,
+) -> Result<Json<UploadResponse>, AppError> {
+    let compiler_version = get_compiler_version(data.to_vec())?;
+    println!("Compiler version: {}", compiler_version);
+    let version = compiler_version
+        .split('.')
+        .next()
+        .and_then(|v| v.parse::<i32>().ok())
+        .ok_or_else(|| AppError::UnsupportedCompilerVersion(compiler_version.clone()))?;
+
+    if version != 0 && version != 2 {
+        return Err(AppError::UnsupportedCompilerVersion(compiler_version));
+    }
+
+    let kind = if version == 2 { "casm" } else { "program" };
+
+    let checksum = hex::encode(Sha256::digest(&data));
+    let compressed = gzip(&data)?;
+
+    if let Some(expected) = headers
+        .get("X-Expected-Sha256")
+        .and_then(|v| v.to_str().ok())
+    {
+        if !expected.eq_ignore_ascii_case(&checksum) {
+            return Err(AppError::ChecksumMismatch);
         }
     }
 
-    if let Some(data) = program_data {
-        println!("Uploading program with version {}", version);
-        if version == 2 {
-            let casm: CasmContractClass = serde_json::from_slice(&data).unwrap();
-            let program_hash = casm.compiled_class_hash();
-            let convert = FieldElement::from_bytes_be(&program_hash.to_be_bytes()).unwrap();
-            program_hash_hex = format!("{:#x}", convert);
-            println!("Program hash: {}", program_hash_hex);
-
-            let id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
-
-            let result = sqlx::query!(
-                "INSERT INTO programs (id, hash, code, version) VALUES ($1, $2, $3, $4)",
-                id,
-                program_hash_hex,
-                data.as_ref(),
-                version
-            )
-            .execute(&*db_pool)
-            .await;
+    println!("Queuing program upload with version {}", version);
+
+    let program_id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
+    let job_id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
+
+    let mut tx = db_pool.begin().await?;
+    queue_hash_job(&mut tx, job_id, program_id, kind).await?;
+
+    sqlx::query!(
+        "INSERT INTO programs (id, code, version, compiler_version, checksum, encoding, job_id, kind) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        program_id,
+        compressed,
+        version,
+        compiler_version,
+        checksum,
+        "gzip",
+        job_id,
+        kind
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(UploadResponse {
+        job_id,
+        casm_job_id: None,
+    }))
+}
+
+/// Uploads a full compiled contract bundle: a Sierra class and its compiled
+/// CASM class, linked by a shared `contract_id` so either hash can be used to
+/// fetch either representation via `get_program`'s `kind` selector.
+async fn upload_bundle(
+    headers: HeaderMap,
+    sierra: Bytes,
+    casm: Bytes,
+    db_pool: Arc<Pool<sqlx::Postgres>>,
+) -> Result<Json<UploadResponse>, AppError> {
+    let sierra_compiler_version = get_compiler_version(sierra.to_vec())?;
+    let sierra_version = sierra_compiler_version
+        .split('.')
+        .next()
+        .and_then(|v| v.parse::<i32>().ok())
+        .ok_or_else(|| AppError::UnsupportedCompilerVersion(sierra_compiler_version.clone()))?;
+
+    let casm_compiler_version = get_compiler_version(casm.to_vec())?;
+    let casm_version = casm_compiler_version
+        .split('.')
+        .next()
+        .and_then(|v| v.parse::<i32>().ok())
+        .ok_or_else(|| AppError::UnsupportedCompilerVersion(casm_compiler_version.clone()))?;
+
+    if casm_version != 0 && casm_version != 2 {
+        return Err(AppError::UnsupportedCompilerVersion(casm_compiler_version));
+    }
+
+    let sierra_checksum = hex::encode(Sha256::digest(&sierra));
+    let casm_checksum = hex::encode(Sha256::digest(&casm));
+
+    if let Some(expected) = headers
+        .get("X-Expected-Sha256")
+        .and_then(|v| v.to_str().ok())
+    {
+        if !expected.eq_ignore_ascii_case(&casm_checksum) {
+            return Err(AppError::ChecksumMismatch);
+        }
+    }
+
+    let sierra_compressed = gzip(&sierra)?;
+    let casm_compressed = gzip(&casm)?;
+
+    let contract_id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
+    let sierra_id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
+    let casm_id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
+    let sierra_job_id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
+    let casm_job_id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
 
-            if result.is_err() {
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut tx = db_pool.begin().await?;
+
+    queue_hash_job(&mut tx, sierra_job_id, sierra_id, "sierra").await?;
+    queue_hash_job(&mut tx, casm_job_id, casm_id, "casm").await?;
+
+    sqlx::query!(
+        "INSERT INTO programs (id, code, version, compiler_version, checksum, encoding, job_id, kind, contract_id) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, 'sierra', $8)",
+        sierra_id,
+        sierra_compressed,
+        sierra_version,
+        sierra_compiler_version,
+        sierra_checksum,
+        "gzip",
+        sierra_job_id,
+        contract_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO programs (id, code, version, compiler_version, checksum, encoding, job_id, kind, contract_id) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, 'casm', $8)",
+        casm_id,
+        casm_compressed,
+        casm_version,
+        casm_compiler_version,
+        casm_checksum,
+        "gzip",
+        casm_job_id,
+        contract_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(UploadResponse {
+        job_id: sierra_job_id,
+        casm_job_id: Some(casm_job_id),
+    }))
+}
+
+async fn queue_hash_job(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    job_id: Uuid,
+    program_id: Uuid,
+    kind: &str,
+) -> Result<(), AppError> {
+    let payload = json!({ "program_id": program_id, "kind": kind });
+
+    sqlx::query!(
+        "INSERT INTO job_queue (id, payload) VALUES ($1, $2)",
+        job_id,
+        payload
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct UploadResponse {
+    job_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    casm_job_id: Option<Uuid>,
+}
+
+/// Status and payload of a hash-computation job, as returned by
+/// `GET /job/{id}`.
+#[derive(Serialize)]
+struct JobStatusResponse {
+    id: Uuid,
+    status: JobStatus,
+    hash: Option<String>,
+    error: Option<String>,
+}
+
+async fn get_job(
+    Path(id): Path<Uuid>,
+    db_pool: Arc<Pool<sqlx::Postgres>>,
+) -> Result<impl IntoResponse, AppError> {
+    let job = sqlx::query!(
+        r#"SELECT status as "status: JobStatus", error FROM job_queue WHERE id = $1"#,
+        id
+    )
+    .fetch_one(&*db_pool)
+    .await?;
+
+    let hash = sqlx::query_scalar!("SELECT hash FROM programs WHERE job_id = $1", id)
+        .fetch_optional(&*db_pool)
+        .await?
+        .flatten();
+
+    Ok(Json(JobStatusResponse {
+        id,
+        status: job.status,
+        hash,
+        error: job.error,
+    }))
+}
+
+/// Status of a `job_queue` row, mirrored from the `job_status` Postgres enum.
+#[derive(sqlx::Type, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[sqlx(type_name = "job_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+enum JobStatus {
+    New,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Deserialize)]
+struct HashJobPayload {
+    program_id: Uuid,
+    kind: String,
+}
+
+/// Pops queued hash jobs with `SELECT ... FOR UPDATE SKIP LOCKED` and runs the
+/// CPU-heavy hash computation on a blocking thread, off the request path.
+fn spawn_hash_job_workers(db_pool: Arc<Pool<sqlx::Postgres>>, worker_count: usize) {
+    for _ in 0..worker_count {
+        let db_pool = Arc::clone(&db_pool);
+        tokio::spawn(async move {
+            loop {
+                match claim_next_job(&db_pool).await {
+                    Ok(Some((job_id, payload))) => {
+                        if let Err(err) = run_hash_job(&db_pool, job_id, payload).await {
+                            tracing::error!(job_id = %job_id, error = %err, "hash job failed");
+                        }
+                    }
+                    Ok(None) => {
+                        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                    }
+                    Err(err) => {
+                        tracing::error!(error = %err, "failed to poll job_queue");
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    }
+                }
             }
-        } else if version == 0 {
-            let program =
-                Program::from_bytes(&data, Some("main")).expect("Could not load program.");
-            let stripped_program = program.get_stripped_program().unwrap();
-            let bootloader_version = 0;
-            let program_hash = compute_program_hash_chain(&stripped_program, bootloader_version)
-                .expect("Failed to compute program hash.");
-
-            program_hash_hex = format!("{:#x}", program_hash);
-            println!("Program Hash: {}", program_hash_hex);
-
-            let id = Uuid::from_bytes(uuid::Uuid::new_v4().to_bytes_le());
-
-            let result = sqlx::query!(
-                "INSERT INTO programs (id, hash, code, version) VALUES ($1, $2, $3, $4)",
-                id,
-                program_hash_hex,
-                data.as_ref(),
-                version
+        });
+    }
+}
+
+/// Periodically requeues jobs stuck in `running` whose `heartbeat` has gone
+/// stale, e.g. because the worker that claimed them crashed mid-job.
+fn spawn_stale_job_reaper(db_pool: Arc<Pool<sqlx::Postgres>>) {
+    const STALE_JOB_INTERVAL: &str = "2 minutes";
+    const SWEEP_PERIOD: std::time::Duration = std::time::Duration::from_secs(30);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(SWEEP_PERIOD).await;
+
+            match reap_stale_jobs(&db_pool, STALE_JOB_INTERVAL).await {
+                Ok(count) if count > 0 => {
+                    tracing::warn!(count, "requeued stale job(s)");
+                }
+                Ok(_) => {}
+                Err(err) => tracing::error!(error = %err, "failed to sweep stale jobs"),
+            }
+        }
+    });
+}
+
+async fn reap_stale_jobs(
+    db_pool: &Pool<sqlx::Postgres>,
+    stale_after: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE job_queue \
+         SET status = 'new', heartbeat = NULL \
+         WHERE status = 'running' \
+           AND heartbeat < now() - $1::interval",
+    )
+    .bind(stale_after)
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn claim_next_job(
+    db_pool: &Pool<sqlx::Postgres>,
+) -> Result<Option<(Uuid, HashJobPayload)>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let row = sqlx::query!(
+        "SELECT id, payload FROM job_queue \
+         WHERE status = 'new' \
+         ORDER BY created_at \
+         FOR UPDATE SKIP LOCKED \
+         LIMIT 1"
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        "UPDATE job_queue SET status = 'running', heartbeat = now() WHERE id = $1",
+        row.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let payload: HashJobPayload = serde_json::from_value(row.payload).map_err(|err| {
+        sqlx::Error::Decode(Box::new(err))
+    })?;
+
+    Ok(Some((row.id, payload)))
+}
+
+/// How often a worker refreshes the `heartbeat` of the job it's actively
+/// processing, so the stale-job reaper can tell a legitimately busy worker
+/// apart from one that crashed mid-job.
+const HEARTBEAT_RENEWAL_PERIOD: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Keeps renewing `job_id`'s heartbeat until the returned handle is aborted.
+/// Run alongside the actual hash computation so a job held by a live worker
+/// never looks stale to `reap_stale_jobs`.
+fn spawn_heartbeat_renewal(db_pool: Pool<sqlx::Postgres>, job_id: Uuid) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(HEARTBEAT_RENEWAL_PERIOD).await;
+
+            if let Err(err) = sqlx::query!(
+                "UPDATE job_queue SET heartbeat = now() WHERE id = $1 AND status = 'running'",
+                job_id
             )
-            .execute(&*db_pool)
+            .execute(&db_pool)
+            .await
+            {
+                tracing::warn!(job_id = %job_id, error = %err, "failed to renew job heartbeat");
+            }
+        }
+    })
+}
+
+async fn run_hash_job(
+    db_pool: &Pool<sqlx::Postgres>,
+    job_id: Uuid,
+    payload: HashJobPayload,
+) -> Result<(), AppError> {
+    let heartbeat_renewal = spawn_heartbeat_renewal(db_pool.clone(), job_id);
+    let result = compute_hash_for_program(db_pool, &payload).await;
+    heartbeat_renewal.abort();
+
+    match result {
+        Ok(hash) => {
+            let update = sqlx::query!(
+                "UPDATE programs SET hash = $1 WHERE id = $2",
+                hash,
+                payload.program_id
+            )
+            .execute(db_pool)
             .await;
 
-            if result.is_err() {
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            // `programs.hash` is UNIQUE, so a byte-identical re-upload of content
+            // that's already been hashed hits this constraint here. That's a
+            // duplicate, not a failure — the hash was computed correctly, it's
+            // just already owned by another row — so let the job still reach a
+            // terminal status instead of erroring out of `run_hash_job` and
+            // looping forever through the stale-job reaper.
+            if let Err(err) = update {
+                if !is_unique_violation(&err) {
+                    return Err(err.into());
+                }
+                tracing::warn!(
+                    job_id = %job_id,
+                    program_id = %payload.program_id,
+                    hash = %hash,
+                    "program hash already recorded on another row, skipping duplicate"
+                );
             }
-        } else {
-            return Err(StatusCode::BAD_REQUEST);
+
+            sqlx::query!(
+                "UPDATE job_queue SET status = 'done', heartbeat = now() WHERE id = $1",
+                job_id
+            )
+            .execute(db_pool)
+            .await?;
+        }
+        Err(err) => {
+            sqlx::query!(
+                "UPDATE job_queue SET status = 'failed', error = $1, heartbeat = now() WHERE id = $2",
+                err.to_string(),
+                job_id
+            )
+            .execute(db_pool)
+            .await?;
         }
     }
 
-    Ok(program_hash_hex)
+    Ok(())
 }
 
-fn get_compiler_version(bytes: Vec<u8>) -> Result<String, Box<dyn std::error::Error>> {
-    let json_str = String::from_utf8(bytes)?;
+fn is_unique_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .is_some_and(|db_err| db_err.is_unique_violation())
+}
 
-    // Parse the JSON string to a serde_json::Value
-    let json_value: serde_json::Value = serde_json::from_str(&json_str)?;
+async fn compute_hash_for_program(
+    db_pool: &Pool<sqlx::Postgres>,
+    payload: &HashJobPayload,
+) -> Result<String, AppError> {
+    let row = sqlx::query!(
+        "SELECT code, encoding FROM programs WHERE id = $1",
+        payload.program_id
+    )
+    .fetch_one(db_pool)
+    .await?;
 
-    // Access the "compiler_version" field and extract its value
-    if let Some(version) = json_value.get("compiler_version").and_then(|v| v.as_str()) {
-        Ok(version.to_string())
+    let code = if row.encoding == "gzip" {
+        gunzip(&row.code)?
     } else {
-        Err("compiler_version field not found or not a uint".into())
+        row.code
+    };
+
+    let kind = payload.kind.clone();
+    tokio::task::spawn_blocking(move || compute_hash(&kind, &code))
+        .await
+        .map_err(|_| AppError::HashComputationFailed)?
+}
+
+/// Dispatches on the job's `kind` alone — never on `version`, which only
+/// disambiguates `upload_single`'s single-blob case and says nothing about
+/// what a bundle upload's `sierra`/`casm` fields actually contain.
+fn compute_hash(kind: &str, data: &[u8]) -> Result<String, AppError> {
+    match kind {
+        "sierra" => compute_sierra_class_hash(data),
+        "casm" => compute_casm_class_hash(data),
+        _ => compute_bootloader_program_hash(data),
     }
 }
+
+fn compute_casm_class_hash(data: &[u8]) -> Result<String, AppError> {
+    let casm: CasmContractClass =
+        serde_json::from_slice(data).map_err(|_| AppError::MalformedCasm)?;
+    let program_hash = casm.compiled_class_hash();
+    let convert = FieldElement::from_bytes_be(&program_hash.to_be_bytes())
+        .map_err(|_| AppError::HashComputationFailed)?;
+    Ok(format!("{:#x}", convert))
+}
+
+fn compute_bootloader_program_hash(data: &[u8]) -> Result<String, AppError> {
+    let program =
+        Program::from_bytes(data, Some("main")).map_err(|_| AppError::MalformedProgram)?;
+    let stripped_program = program
+        .get_stripped_program()
+        .map_err(|_| AppError::HashComputationFailed)?;
+    let program_hash = compute_program_hash_chain(&stripped_program, 0)
+        .map_err(|_| AppError::HashComputationFailed)?;
+    Ok(format!("{:#x}", program_hash))
+}
+
+fn compute_sierra_class_hash(data: &[u8]) -> Result<String, AppError> {
+    let sierra: SierraContractClass =
+        serde_json::from_slice(data).map_err(|_| AppError::MalformedSierra)?;
+    let class_hash = sierra
+        .class_hash()
+        .map_err(|_| AppError::HashComputationFailed)?;
+    let convert = FieldElement::from_bytes_be(&class_hash.to_be_bytes())
+        .map_err(|_| AppError::HashComputationFailed)?;
+    Ok(format!("{:#x}", convert))
+}
+
+fn get_compiler_version(bytes: Vec<u8>) -> Result<String, AppError> {
+    let json_str = String::from_utf8(bytes).map_err(|_| AppError::InvalidMultipart)?;
+
+    // Parse the JSON string to a serde_json::Value
+    let json_value: serde_json::Value =
+        serde_json::from_str(&json_str).map_err(|_| AppError::InvalidMultipart)?;
+
+    // Access the "compiler_version" field and extract its value
+    json_value
+        .get("compiler_version")
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_string())
+        .ok_or(AppError::InvalidMultipart)
+}